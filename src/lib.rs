@@ -17,7 +17,12 @@
 //! * Fixed memory footprint (520 bytes) with no dynamic allocations
 //! * Constant time record and retrieve operations that compile down to a few instructions
 //! * `no_std` support
-//! * Work in progress: Compact binary serialization
+//! * Compact binary serialization that exploits the sparsity of populated buckets
+//! * Quantile queries, histogram aggregation (`add`/`subtract`), and summary
+//!   statistics (min/max/mean/stddev)
+//! * `Display` as an ASCII bar chart, and a cumulative bucket iterator for
+//!   rendering CDFs
+//! * A lock-free `AtomicBase2Histogram` variant for concurrent recording
 //!
 //! # Example
 //!
@@ -109,6 +114,9 @@
 
 #![no_std]
 
+#[cfg(feature = "atomic64")]
+use core::sync::atomic::{AtomicU64, Ordering};
+
 ///
 /// A compact and efficient integer histogram with fixed memory footprint,
 /// constant runtime performance, and very compact binary serialization.
@@ -134,6 +142,71 @@ pub struct Bucket {
     pub count: u64,
 }
 
+/// Rounds `x` (assumed non-negative) up to the nearest integer.
+///
+/// `core` has no `f64::ceil()` since it requires a libm binding that isn't available
+/// without `std`, so this crate provides its own to stay `no_std`.
+#[inline]
+fn ceil_f64(x: f64) -> f64 {
+    let truncated = x as u64 as f64;
+    if x > truncated {
+        truncated + 1.0
+    } else {
+        truncated
+    }
+}
+
+/// Errors produced while serializing or deserializing a `Base2Histogram`.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Error {
+    /// The buffer passed to [`serialize()`](struct.Base2Histogram.html#method.serialize)
+    /// was too small to hold the encoded histogram
+    BufferTooSmall,
+    /// The buffer passed to [`deserialize()`](struct.Base2Histogram.html#method.deserialize)
+    /// ended before a complete histogram could be read
+    UnexpectedEof,
+    /// The version tag in the buffer was not one this crate knows how to decode
+    UnsupportedVersion(u8),
+    /// [`subtract()`](struct.Base2Histogram.html#method.subtract) was given a
+    /// histogram with more observations in a bucket than `self` has
+    Underflow,
+}
+
+/// Version tag written as the first byte of the serialized format.
+const SERIALIZE_VERSION: u8 = 1;
+
+/// Returns the square root of `x`, or `0.0` if `x` is not positive.
+///
+/// `core` has no `f64::sqrt()` for the same reason it has no `ceil()` (see
+/// [`ceil_f64`]); this uses Newton's method, which converges to full `f64`
+/// precision in well under 64 iterations for any finite, positive input.
+#[inline]
+fn sqrt_f64(x: f64) -> f64 {
+    if x <= 0.0 {
+        return 0.0;
+    }
+
+    // Halve the exponent bits for a starting guess within a small factor of the root.
+    let mut guess = f64::from_bits((x.to_bits() >> 1) + 0x1FF8_0000_0000_0000);
+    let mut i = 0;
+    while i < 64 {
+        guess = 0.5 * (guess + x / guess);
+        i += 1;
+    }
+
+    guess
+}
+
+/// Returns the bucket index for `value`, shared by `Base2Histogram` and
+/// `AtomicBase2Histogram` so their bucket layouts stay identical.
+#[inline]
+fn index_of(value: u64) -> usize {
+    match u64::leading_zeros(value) {
+        0 => 63 as usize,
+        clz => (64 - clz) as usize
+    }
+}
+
 impl Base2Histogram {
     /// Create a new `Base2Histogram` instance
     pub fn new() -> Self {
@@ -152,19 +225,56 @@ impl Base2Histogram {
     /// Record `count` observations of `value`
     #[inline]
     pub fn record_n(&mut self, value: u64, count: u64) {
-        let idx = self.index_of(value);
+        let idx = index_of(value);
 
         self.counts[idx] = self.counts[idx].saturating_add(count);
         self.mask |= 1 << (idx as u64);
     }
 
+    /// Add the bucket counts of `other` into `self`, saturating on overflow.
+    ///
+    /// Useful for aggregating per-shard or per-interval histograms into a single,
+    /// global view. Because the bucket layout is fixed and identical for every
+    /// instance, this is an O(64) operation with no allocation.
+    pub fn add(&mut self, other: &Base2Histogram) {
+        for idx in 0..64 {
+            self.counts[idx] = self.counts[idx].saturating_add(other.counts[idx]);
+        }
+        self.mask |= other.mask;
+    }
+
+    /// Subtract the bucket counts of `other` from `self`.
+    ///
+    /// Returns [`Error::Underflow`](enum.Error.html) without modifying `self` if any
+    /// bucket in `other` has more observations than the corresponding bucket in
+    /// `self`, so callers can't silently corrupt a delta.
+    pub fn subtract(&mut self, other: &Base2Histogram) -> Result<(), Error> {
+        for idx in 0..64 {
+            if other.counts[idx] > self.counts[idx] {
+                return Err(Error::Underflow);
+            }
+        }
+
+        for idx in 0..64 {
+            self.counts[idx] = self.counts[idx].saturating_sub(other.counts[idx]);
+
+            if self.counts[idx] != 0 {
+                self.mask |= 1 << idx;
+            } else {
+                self.mask &= !(1 << idx);
+            }
+        }
+
+        Ok(())
+    }
+
     /// Returns the number of observations recorded by the bucket containing `value`
     ///
     /// To retrieve the number of observations along with its bucket bounds, see
     /// [`bucket_for()`](struct.Base2Histogram.html#method.bucket_for).
     #[inline]
     pub fn observations(&self, value: u64) -> u64 {
-        let idx = self.index_of(value);
+        let idx = index_of(value);
         self.counts[idx]
     }
 
@@ -174,7 +284,7 @@ impl Base2Histogram {
     /// [`observations()`](struct.Base2Histogram.html#method.observations).
     #[inline]
     pub fn bucket_for(&self, value: u64) -> Bucket {
-        let idx = self.index_of(value);
+        let idx = index_of(value);
         self.bucket_at(idx)
     }
 
@@ -187,7 +297,7 @@ impl Base2Histogram {
     /// Returns `true` if the bucket count corresponding to `value` is non-zero
     #[inline]
     pub fn has_counts(&self, value: u64) -> bool {
-        let idx = self.index_of(value) as u64;
+        let idx = index_of(value) as u64;
         self.mask & (1 << idx) != 0
     }
 
@@ -206,15 +316,225 @@ impl Base2Histogram {
         })
     }
 
-    /// Returns the bucket index into `self.counts` for the `value`
+    /// Returns an iterator of `(upper_bound, cumulative_count)` pairs in the
+    /// Prometheus "less-or-equal" style: the second element of each pair is the
+    /// running total of all observations at or below the paired bucket boundary,
+    /// ending with `(u64::MAX, total_count())`. This lets callers emit the
+    /// power-of-2 boundaries directly as `le` thresholds.
+    ///
+    /// When `nonzero_only` is `true`, boundaries whose bucket had no observations
+    /// (and so would duplicate the previous cumulative count) are skipped; the
+    /// final `(u64::MAX, total_count())` boundary is always yielded.
+    ///
+    /// `nonzero_only` is a required parameter: call `iter_cumulative(false)` to
+    /// get all 64 boundaries.
+    pub fn iter_cumulative(&self, nonzero_only: bool) -> impl Iterator<Item=(u64, u64)> + '_ {
+        let mut idx = 0;
+        let mut cumulative = 0u64;
+
+        core::iter::from_fn(move || {
+            while idx < 64 {
+                let bucket = self.bucket_at(idx);
+                cumulative = cumulative.saturating_add(bucket.count);
+
+                let is_last = idx == 63;
+                let bound = if is_last { u64::MAX } else { bucket.end };
+                let skip = !is_last && nonzero_only && bucket.count == 0;
+                idx += 1;
+
+                if !skip {
+                    return Some((bound, cumulative));
+                }
+            }
+            None
+        })
+    }
+
+    /// Returns the total number of observations recorded across all buckets
     #[inline]
-    fn index_of(&self, value: u64) -> usize {
-        match u64::leading_zeros(value) {
-            0 => 63 as usize,
-            clz => (64 - clz) as usize
+    pub fn total_count(&self) -> u64 {
+        self.counts.iter().fold(0u64, |acc, &c| acc.saturating_add(c))
+    }
+
+    /// Returns the value at or below which a fraction `q` (in `[0.0, 1.0]`) of all
+    /// recorded observations fall.
+    ///
+    /// For example `value_at_quantile(0.99)` returns the p99 value. Returns `0` if
+    /// the histogram has no observations.
+    pub fn value_at_quantile(&self, q: f64) -> u64 {
+        let total = self.total_count();
+        if total == 0 {
+            return 0;
+        }
+
+        let target = ceil_f64(q * total as f64);
+        let target = if target < 1.0 { 1u64 } else { target as u64 };
+        let target = if target > total { total } else { target };
+
+        let mut cumulative = 0u64;
+        for idx in 0..64 {
+            cumulative = cumulative.saturating_add(self.counts[idx]);
+            if cumulative >= target {
+                return self.bucket_at(idx).end;
+            }
+        }
+
+        self.bucket_at(63).end
+    }
+
+    /// Returns the quantile (in `[0.0, 1.0]`) at or below which `value` falls.
+    ///
+    /// Returns `0.0` if the histogram has no observations.
+    pub fn quantile_of(&self, value: u64) -> f64 {
+        let total = self.total_count();
+        if total == 0 {
+            return 0.0;
+        }
+
+        let idx = index_of(value);
+        let cumulative = self.counts[0..=idx]
+            .iter()
+            .fold(0u64, |acc, &c| acc.saturating_add(c));
+
+        cumulative as f64 / total as f64
+    }
+
+    /// Returns the start of the lowest bucket with one or more observations, or `0`
+    /// if the histogram is empty.
+    pub fn min(&self) -> u64 {
+        for idx in 0..64 {
+            if self.mask & (1 << idx) != 0 {
+                return self.bucket_at(idx).start;
+            }
+        }
+        0
+    }
+
+    /// Returns the end of the highest bucket with one or more observations, or `0`
+    /// if the histogram is empty.
+    pub fn max(&self) -> u64 {
+        for idx in (0..64).rev() {
+            if self.mask & (1 << idx) != 0 {
+                return self.bucket_at(idx).end;
+            }
+        }
+        0
+    }
+
+    /// Returns the approximate arithmetic mean of all recorded observations.
+    ///
+    /// Derived in a single pass by treating each populated bucket's midpoint as
+    /// representative of every observation within it. Returns `0.0` if the
+    /// histogram is empty.
+    pub fn mean(&self) -> f64 {
+        let total = self.total_count();
+        if total == 0 {
+            return 0.0;
+        }
+
+        let sum: f64 = (0..64)
+            .filter(|&idx| self.mask & (1 << idx) != 0)
+            .map(|idx| self.midpoint(idx) * self.counts[idx] as f64)
+            .sum();
+
+        sum / total as f64
+    }
+
+    /// Returns the approximate standard deviation of all recorded observations,
+    /// derived from the same per-bucket midpoints used by [`mean()`](#method.mean).
+    /// Returns `0.0` if the histogram is empty.
+    pub fn stddev(&self) -> f64 {
+        let total = self.total_count();
+        if total == 0 {
+            return 0.0;
+        }
+
+        let mean = self.mean();
+        let sum_sq: f64 = (0..64)
+            .filter(|&idx| self.mask & (1 << idx) != 0)
+            .map(|idx| {
+                let mid = self.midpoint(idx);
+                mid * mid * self.counts[idx] as f64
+            })
+            .sum();
+
+        let variance = sum_sq / total as f64 - mean * mean;
+
+        if variance > 0.0 {
+            sqrt_f64(variance)
+        } else {
+            0.0
+        }
+    }
+
+    /// Serialize this histogram into `out`, returning the number of bytes written.
+    ///
+    /// The encoding only stores populated buckets, so a histogram touching only a
+    /// handful of the 64 buckets serializes to well under the 520 bytes the in-memory
+    /// representation occupies. The format is a 1-byte version tag, the 8-byte `mask`
+    /// (little-endian), then one LEB128 varint per set bit of `mask` (walking from the
+    /// least-significant bit to the most) holding that bucket's count.
+    ///
+    /// Returns [`Error::BufferTooSmall`](enum.Error.html) if `out` is not large enough.
+    pub fn serialize(&self, out: &mut [u8]) -> Result<usize, Error> {
+        let mut pos = 0;
+
+        if out.is_empty() {
+            return Err(Error::BufferTooSmall);
+        }
+        out[pos] = SERIALIZE_VERSION;
+        pos += 1;
+
+        if out.len() < pos + 8 {
+            return Err(Error::BufferTooSmall);
+        }
+        out[pos..pos + 8].copy_from_slice(&self.mask.to_le_bytes());
+        pos += 8;
+
+        for idx in 0..64 {
+            if self.mask & (1 << idx) != 0 {
+                pos = write_varint(out, pos, self.counts[idx])?;
+            }
+        }
+
+        Ok(pos)
+    }
+
+    /// Deserialize a histogram previously written by
+    /// [`serialize()`](struct.Base2Histogram.html#method.serialize).
+    ///
+    /// Returns [`Error::UnexpectedEof`](enum.Error.html) if `buf` is truncated, or
+    /// [`Error::UnsupportedVersion`](enum.Error.html) if its version tag isn't recognized.
+    pub fn deserialize(buf: &[u8]) -> Result<Base2Histogram, Error> {
+        let mut pos = 0;
+
+        let tag = *buf.get(pos).ok_or(Error::UnexpectedEof)?;
+        if tag != SERIALIZE_VERSION {
+            return Err(Error::UnsupportedVersion(tag));
+        }
+        pos += 1;
+
+        if buf.len() < pos + 8 {
+            return Err(Error::UnexpectedEof);
         }
+        let mut mask_bytes = [0u8; 8];
+        mask_bytes.copy_from_slice(&buf[pos..pos + 8]);
+        let mask = u64::from_le_bytes(mask_bytes);
+        pos += 8;
+
+        let mut counts = [0u64; 64];
+        for (idx, slot) in counts.iter_mut().enumerate() {
+            if mask & (1 << idx) != 0 {
+                let (value, new_pos) = read_varint(buf, pos)?;
+                *slot = value;
+                pos = new_pos;
+            }
+        }
+
+        Ok(Base2Histogram { counts, mask })
     }
 
+
     /// Return the `Bucket` at the provided index (index values 0..63)
     fn bucket_at(&self, idx: usize) -> Bucket {
         if idx == 0 {
@@ -228,4 +548,191 @@ impl Base2Histogram {
             Bucket { start: begin, end, count }
         }
     }
+
+    /// Returns the representative midpoint value of the bucket at `idx`, used to
+    /// estimate mean and variance without iterating raw observations. The zero and
+    /// one buckets use their `start` since they each cover a single value.
+    fn midpoint(&self, idx: usize) -> f64 {
+        let bucket = self.bucket_at(idx);
+
+        if idx == 0 || idx == 1 {
+            bucket.start as f64
+        } else {
+            bucket.start as f64 + (bucket.end as f64 - bucket.start as f64) / 2.0
+        }
+    }
+}
+
+/// Maximum width, in characters, of the bars printed by the `Display` implementation.
+const DISPLAY_BAR_WIDTH: u64 = 40;
+
+impl core::fmt::Display for Base2Histogram {
+    /// Renders an ASCII bar chart of the populated buckets, e.g.:
+    ///
+    /// ```text
+    /// count=9 min=0 max=15 mean=7.89
+    /// [         0,          0]          1 |######
+    /// [         1,          1]          2 |#############
+    /// [         8,         15]          6 |########################################
+    /// ```
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        writeln!(
+            f,
+            "count={} min={} max={} mean={:.2}",
+            self.total_count(),
+            self.min(),
+            self.max(),
+            self.mean()
+        )?;
+
+        let max_count = (0..64)
+            .filter(|&idx| self.mask & (1 << idx) != 0)
+            .map(|idx| self.counts[idx])
+            .max()
+            .unwrap_or(0);
+
+        for idx in 0..64 {
+            if self.mask & (1 << idx) == 0 {
+                continue;
+            }
+
+            let bucket = self.bucket_at(idx);
+            let bar_len = if max_count == 0 {
+                0
+            } else {
+                // Widen to u128 first: `count` can legitimately saturate up to
+                // `u64::MAX` (see "Overflow Behavior" above), and `count * DISPLAY_BAR_WIDTH`
+                // would overflow `u64` before the division brings it back down.
+                (bucket.count as u128 * DISPLAY_BAR_WIDTH as u128 / max_count as u128) as u64
+            };
+
+            write!(f, "[{:>10}, {:>10}] {:>10} |", bucket.start, bucket.end, bucket.count)?;
+            for _ in 0..bar_len {
+                write!(f, "#")?;
+            }
+            writeln!(f)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Write `value` into `buf` at `pos` as an unsigned LEB128 varint, returning the
+/// position immediately following the written bytes.
+fn write_varint(buf: &mut [u8], mut pos: usize, mut value: u64) -> Result<usize, Error> {
+    loop {
+        if pos >= buf.len() {
+            return Err(Error::BufferTooSmall);
+        }
+
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+
+        if value == 0 {
+            buf[pos] = byte;
+            pos += 1;
+            return Ok(pos);
+        } else {
+            buf[pos] = byte | 0x80;
+            pos += 1;
+        }
+    }
+}
+
+/// Read an unsigned LEB128 varint from `buf` starting at `pos`, returning the
+/// decoded value and the position immediately following it.
+fn read_varint(buf: &[u8], mut pos: usize) -> Result<(u64, usize), Error> {
+    let mut value = 0u64;
+    let mut shift = 0u32;
+
+    loop {
+        let byte = *buf.get(pos).ok_or(Error::UnexpectedEof)?;
+        pos += 1;
+
+        value |= ((byte & 0x7f) as u64) << shift;
+
+        if byte & 0x80 == 0 {
+            return Ok((value, pos));
+        }
+
+        shift += 7;
+    }
+}
+
+/// A lock-free variant of [`Base2Histogram`](struct.Base2Histogram.html) that
+/// records observations through `&self`, making it safe to share across threads
+/// without external locking (the way Twitter's and tokio's histogram types do).
+///
+/// Bucket counts live in `[AtomicU64; 64]` and the populated-bucket mask lives in
+/// an `AtomicU64`, both updated with `Ordering::Relaxed` since buckets are
+/// independent counters with no ordering relationship to enforce. Call
+/// [`snapshot()`](#method.snapshot) to get a point-in-time, immutable
+/// `Base2Histogram` for querying or serialization.
+///
+/// Gated behind the `atomic64` feature since it requires 64-bit atomics, which
+/// aren't available on every `no_std` target.
+#[cfg(feature = "atomic64")]
+pub struct AtomicBase2Histogram {
+    counts: [AtomicU64; 64],
+    mask: AtomicU64,
+}
+
+#[cfg(feature = "atomic64")]
+impl AtomicBase2Histogram {
+    /// Create a new `AtomicBase2Histogram` instance
+    pub fn new() -> Self {
+        AtomicBase2Histogram {
+            counts: [(); 64].map(|_| AtomicU64::new(0)),
+            mask: AtomicU64::new(0),
+        }
+    }
+
+    /// Record a single observation of `value`
+    #[inline]
+    pub fn record(&self, value: u64) {
+        self.record_n(value, 1);
+    }
+
+    /// Record `count` observations of `value`
+    #[inline]
+    pub fn record_n(&self, value: u64, count: u64) {
+        let idx = index_of(value);
+
+        let mut current = self.counts[idx].load(Ordering::Relaxed);
+        loop {
+            let updated = current.saturating_add(count);
+            match self.counts[idx].compare_exchange_weak(
+                current,
+                updated,
+                Ordering::Relaxed,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => break,
+                Err(observed) => current = observed,
+            }
+        }
+
+        self.mask.fetch_or(1 << idx, Ordering::Relaxed);
+    }
+
+    /// Returns an immutable, point-in-time [`Base2Histogram`](struct.Base2Histogram.html)
+    /// built by reading each atomic counter with `Ordering::Relaxed`.
+    pub fn snapshot(&self) -> Base2Histogram {
+        let mut counts = [0u64; 64];
+        for (idx, slot) in counts.iter_mut().enumerate() {
+            *slot = self.counts[idx].load(Ordering::Relaxed);
+        }
+
+        Base2Histogram {
+            counts,
+            mask: self.mask.load(Ordering::Relaxed),
+        }
+    }
+}
+
+#[cfg(feature = "atomic64")]
+impl Default for AtomicBase2Histogram {
+    fn default() -> Self {
+        Self::new()
+    }
 }
@@ -153,6 +153,246 @@ mod test {
         assert_eq!(b.count, 1);
     }
 
+    #[test]
+    fn quantile_of_empty_histogram_is_zero() {
+        let hist = Base2Histogram::new();
+        assert_eq!(hist.total_count(), 0);
+        assert_eq!(hist.value_at_quantile(0.5), 0);
+        assert_eq!(hist.quantile_of(100), 0.0);
+    }
+
+    #[test]
+    fn value_at_quantile_picks_correct_bucket() {
+        let mut hist = Base2Histogram::new();
+
+        for _ in 0..99 {
+            hist.record(1);
+        }
+        hist.record(1000);
+
+        assert_eq!(hist.total_count(), 100);
+        assert_eq!(hist.value_at_quantile(0.99), 1);
+        assert_eq!(hist.value_at_quantile(1.0), hist.bucket_for(1000).end);
+    }
+
+    #[test]
+    fn quantile_of_matches_cumulative_fraction() {
+        let mut hist = Base2Histogram::new();
+
+        hist.record_n(1, 50);
+        hist.record_n(2, 50);
+
+        assert_eq!(hist.quantile_of(1), 0.5);
+        assert_eq!(hist.quantile_of(2), 1.0);
+    }
+
+    #[test]
+    fn serialize_roundtrip_preserves_counts() {
+        use b2histogram::Error;
+
+        let mut hist = Base2Histogram::new();
+        hist.record(0);
+        hist.record_n(11, 2);
+        hist.record_n(300_000, 6);
+
+        let mut buf = [0u8; 64];
+        let n = hist.serialize(&mut buf).unwrap();
+        assert!(n < 50);
+
+        let restored = Base2Histogram::deserialize(&buf[..n]).unwrap();
+        assert_eq!(restored.observations(0), 1);
+        assert_eq!(restored.observations(11), 2);
+        assert_eq!(restored.observations(300_000), 6);
+        assert_eq!(restored.nonzero_buckets(), hist.nonzero_buckets());
+
+        let mut too_small = [0u8; 2];
+        assert_eq!(hist.serialize(&mut too_small), Err(Error::BufferTooSmall));
+
+        match Base2Histogram::deserialize(&[]) {
+            Err(Error::UnexpectedEof) => {}
+            other => panic!("expected UnexpectedEof, got {:?}", other.map(|_| ())),
+        }
+        match Base2Histogram::deserialize(&[99]) {
+            Err(Error::UnsupportedVersion(99)) => {}
+            other => panic!("expected UnsupportedVersion(99), got {:?}", other.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn add_combines_counts_from_both_histograms() {
+        let mut a = Base2Histogram::new();
+        a.record_n(1, 5);
+        a.record_n(1000, 2);
+
+        let mut b = Base2Histogram::new();
+        b.record_n(1, 3);
+        b.record_n(50, 1);
+
+        a.add(&b);
+
+        assert_eq!(a.observations(1), 8);
+        assert_eq!(a.observations(1000), 2);
+        assert_eq!(a.observations(50), 1);
+        assert_eq!(a.nonzero_buckets(), 3);
+    }
+
+    #[test]
+    fn subtract_removes_counts_and_clears_empty_buckets() {
+        let mut a = Base2Histogram::new();
+        a.record_n(1, 5);
+        a.record_n(1000, 2);
+
+        let mut b = Base2Histogram::new();
+        b.record_n(1, 5);
+
+        a.subtract(&b).unwrap();
+
+        assert_eq!(a.observations(1), 0);
+        assert_eq!(a.has_counts(1), false);
+        assert_eq!(a.observations(1000), 2);
+        assert_eq!(a.nonzero_buckets(), 1);
+    }
+
+    #[test]
+    fn subtract_rejects_a_larger_subtrahend() {
+        use b2histogram::Error;
+
+        let mut a = Base2Histogram::new();
+        a.record_n(1, 2);
+
+        let mut b = Base2Histogram::new();
+        b.record_n(1, 3);
+
+        assert_eq!(a.subtract(&b), Err(Error::Underflow));
+        assert_eq!(a.observations(1), 2, "self is left unmodified on error");
+    }
+
+    #[test]
+    fn summary_stats_of_empty_histogram_are_zero() {
+        let hist = Base2Histogram::new();
+        assert_eq!(hist.min(), 0);
+        assert_eq!(hist.max(), 0);
+        assert_eq!(hist.mean(), 0.0);
+        assert_eq!(hist.stddev(), 0.0);
+    }
+
+    #[test]
+    fn min_and_max_track_populated_buckets() {
+        let mut hist = Base2Histogram::new();
+        hist.record(11);
+        hist.record(1000);
+
+        assert_eq!(hist.min(), hist.bucket_for(11).start);
+        assert_eq!(hist.max(), hist.bucket_for(1000).end);
+    }
+
+    #[test]
+    fn mean_of_single_bucket_equals_its_midpoint() {
+        let mut hist = Base2Histogram::new();
+        hist.record_n(0, 100);
+
+        assert_eq!(hist.mean(), 0.0);
+        assert_eq!(hist.stddev(), 0.0);
+    }
+
+    #[test]
+    fn mean_and_stddev_of_mixed_buckets_are_nonzero() {
+        let mut hist = Base2Histogram::new();
+        hist.record_n(1, 50);
+        hist.record_n(1000, 50);
+
+        let mean = hist.mean();
+        assert!(mean > 1.0 && mean < 1000.0);
+        assert!(hist.stddev() > 0.0);
+    }
+
+    #[test]
+    fn display_renders_a_header_and_one_line_per_populated_bucket() {
+        let mut hist = Base2Histogram::new();
+        hist.record(0);
+        hist.record_n(11, 2);
+
+        let rendered = format!("{}", hist);
+        let mut lines = rendered.lines();
+
+        let header = lines.next().unwrap();
+        assert!(header.starts_with("count=3"));
+        assert!(header.contains("min=0"));
+        assert!(header.contains("max="));
+        assert!(header.contains("mean="));
+
+        assert_eq!(lines.clone().count(), hist.nonzero_buckets() as usize);
+        assert!(lines.next().unwrap().contains('|'));
+    }
+
+    #[test]
+    fn display_does_not_overflow_with_a_saturated_bucket_count() {
+        let mut hist = Base2Histogram::new();
+        hist.record_n(1, u64::max_value());
+
+        let rendered = format!("{}", hist);
+        assert!(rendered.contains(&u64::max_value().to_string()));
+    }
+
+    #[test]
+    fn iter_cumulative_is_monotonically_increasing_and_ends_at_total() {
+        let mut hist = Base2Histogram::new();
+        hist.record_n(1, 5);
+        hist.record_n(1000, 3);
+
+        let pairs: Vec<(u64, u64)> = hist.iter_cumulative(false).collect();
+        assert_eq!(pairs.len(), 64);
+
+        let mut prev = 0;
+        for (_, cumulative) in &pairs {
+            assert!(*cumulative >= prev);
+            prev = *cumulative;
+        }
+
+        assert_eq!(pairs.last().unwrap(), &(u64::max_value(), hist.total_count()));
+    }
+
+    #[test]
+    fn iter_cumulative_nonzero_only_skips_empty_buckets() {
+        let mut hist = Base2Histogram::new();
+        hist.record_n(1, 5);
+        hist.record_n(1000, 3);
+
+        let pairs: Vec<(u64, u64)> = hist.iter_cumulative(true).collect();
+
+        // The final (u64::MAX, total) boundary is always kept, even if the
+        // top-most bucket itself has no observations.
+        assert_eq!(pairs.len(), hist.nonzero_buckets() as usize + 1);
+        assert_eq!(pairs.last().unwrap(), &(u64::max_value(), hist.total_count()));
+    }
+
+    #[test]
+    #[cfg(feature = "atomic64")]
+    fn atomic_histogram_snapshot_matches_recorded_counts() {
+        use b2histogram::AtomicBase2Histogram;
+        use std::sync::Arc;
+        use std::thread;
+
+        let hist = Arc::new(AtomicBase2Histogram::new());
+        let mut handles = Vec::new();
+
+        for _ in 0..4 {
+            let hist = Arc::clone(&hist);
+            handles.push(thread::spawn(move || {
+                for _ in 0..250 {
+                    hist.record(11);
+                }
+            }));
+        }
+
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        let snapshot = hist.snapshot();
+        assert_eq!(snapshot.observations(11), 1000);
+    }
+
     #[test]
     fn iterating_buckets_is_successful() {
         let mut hist = Base2Histogram::new();